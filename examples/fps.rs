@@ -14,23 +14,67 @@ impl Cam {
         vec3(yaw.sin() * pitch.cos(), pitch.sin(), yaw.cos() * pitch.cos())
     }
 
+    fn right(&self) -> Vec3 {
+        let yaw = self.yaw_deg.to_radians();
+        vec3(-yaw.cos(), 0.0, yaw.sin())
+    }
+
     fn turn(&mut self, pitch_delta_deg: f32, yaw_delta_deg: f32) {
         self.pitch_deg = (self.pitch_deg + pitch_delta_deg).max(-89.0).min(89.0);
         self.yaw_deg = (self.yaw_deg + yaw_delta_deg) % 360.0;
     }
 }
 
-const MAX_OBSTACLES: usize = 512 * 1024;
+const MOVE_SPEED: f32 = 4.0;
+
+// a local elapsed-time/delta clock; `EventHandler` doesn't pass one in
+struct FrameClock {
+    start: std::time::Instant,
+    last: std::time::Instant,
+}
+
+impl FrameClock {
+    fn new() -> FrameClock {
+        let now = std::time::Instant::now();
+        FrameClock { start: now, last: now }
+    }
+
+    // seconds elapsed since the clock was created
+    fn time(&self) -> f32 {
+        self.start.elapsed().as_secs_f32()
+    }
+
+    // seconds since the previous call to `tick`
+    fn tick(&mut self) -> f32 {
+        let now = std::time::Instant::now();
+        let delta = (now - self.last).as_secs_f32();
+        self.last = now;
+        delta
+    }
+}
+
 struct Stage {
     pipeline: Pipeline,
     bindings: Bindings,
-    obstacles: Vec<Vec3>,
+    obstacles: BufferVec<Vec3>,
     cam: Cam,
     pos: Vec3,
+    clock: FrameClock,
+    held_keys: std::collections::HashSet<KeyCode>,
+    mouse_pos: (f32, f32),
+    // obstacles are rendered offscreen first, then sampled onto a fullscreen quad
+    offscreen_pass: RenderPass,
+    quad_pipeline: Pipeline,
+    quad_bindings: Bindings,
 }
 
 impl Stage {
     pub fn new(ctx: &mut Context) -> Stage {
+        // `Shader::new_with_geometry`/`PrimitiveType` don't exist in published miniquad,
+        // and there's no crate source in this tree to add them to, so the bipyramid is
+        // back to a hand-authored static vertex/index buffer, instanced per obstacle —
+        // real geometry-shader stage support is out of scope until the engine itself
+        // (not present here) grows it.
         let r = 0.3;
         #[rustfmt::skip]
         let vertices: &[f32] = &[
@@ -52,16 +96,12 @@ impl Stage {
         ];
         let index_buffer = Buffer::immutable(ctx, BufferType::IndexBuffer, &indices);
 
-        // empty, dynamic instance-data vertex buffer
-        let positions_vertex_buffer = Buffer::stream(
-            ctx,
-            BufferType::VertexBuffer,
-            MAX_OBSTACLES * std::mem::size_of::<Vec3>(),
-        );
+        // empty, dynamic instance-data vertex buffer; grows on its own as obstacles are added
+        let positions_vertex_buffer = BufferVec::new(ctx, BufferType::VertexBuffer);
 
         let bindings = Bindings {
-            vertex_buffers: vec![geometry_vertex_buffer, positions_vertex_buffer],
-            index_buffer: index_buffer,
+            vertex_buffers: vec![geometry_vertex_buffer, positions_vertex_buffer.buffer()],
+            index_buffer,
             images: vec![],
         };
 
@@ -89,7 +129,7 @@ impl Stage {
             }
         );
 
-        let mut obstacles = vec![];
+        let mut obstacles = positions_vertex_buffer;
 
         for x in 0..10 {
             for y in 0..10 {
@@ -100,12 +140,74 @@ impl Stage {
             }
         }
 
+        // `TextureParams` has no MSAA/`sample_count` field and `RenderPass` has no resolve
+        // step in published miniquad (MSAA is window-level only, via `conf::Conf`), so this
+        // is a plain single-sample color attachment plus a depth attachment so the offscreen
+        // pass depth-tests just like the default one
+        let (width, height) = ctx.screen_size();
+        let offscreen_color = Texture::new_render_texture(
+            ctx,
+            TextureParams {
+                width: width as u32,
+                height: height as u32,
+                format: TextureFormat::RGBA8,
+                ..Default::default()
+            },
+        );
+        let offscreen_depth = Texture::new_render_texture(
+            ctx,
+            TextureParams {
+                width: width as u32,
+                height: height as u32,
+                format: TextureFormat::Depth,
+                ..Default::default()
+            },
+        );
+        let offscreen_pass = RenderPass::new(ctx, offscreen_color, offscreen_depth);
+
+        #[rustfmt::skip]
+        let quad_vertices: &[f32] = &[
+            // positions    uvs
+            -1.0, -1.0,     0.0, 0.0,
+             1.0, -1.0,     1.0, 0.0,
+             1.0,  1.0,     1.0, 1.0,
+            -1.0,  1.0,     0.0, 1.0,
+        ];
+        let quad_vertex_buffer = Buffer::immutable(ctx, BufferType::VertexBuffer, &quad_vertices);
+        let quad_indices: &[u16] = &[0, 1, 2, 0, 2, 3];
+        let quad_index_buffer = Buffer::immutable(ctx, BufferType::IndexBuffer, &quad_indices);
+
+        let quad_bindings = Bindings {
+            vertex_buffers: vec![quad_vertex_buffer],
+            index_buffer: quad_index_buffer,
+            images: vec![offscreen_color],
+        };
+
+        let quad_shader =
+            Shader::new(ctx, quad_shader::VERTEX, quad_shader::FRAGMENT, quad_shader::meta())
+                .unwrap();
+        let quad_pipeline = Pipeline::new(
+            ctx,
+            &[BufferLayout::default()],
+            &[
+                VertexAttribute::new("pos", VertexFormat::Float2),
+                VertexAttribute::new("uv", VertexFormat::Float2),
+            ],
+            quad_shader,
+        );
+
         Stage {
             pipeline,
             bindings,
             obstacles,
             pos: Vec3::zero(),
             cam: Default::default(),
+            clock: FrameClock::new(),
+            held_keys: std::collections::HashSet::new(),
+            mouse_pos: (0.0, 0.0),
+            offscreen_pass,
+            quad_pipeline,
+            quad_bindings,
         }
     }
 }
@@ -113,13 +215,47 @@ impl Stage {
 impl EventHandler for Stage {
     fn update(&mut self, ctx: &mut Context) {
         ctx.set_cursor_grab(true);
-        self.bindings.vertex_buffers[1].update(ctx, &self.obstacles[..]);
+
+        // move at a fixed speed regardless of frame rate, rather than a fixed
+        // per-frame step that would run faster on faster machines
+        let delta = self.clock.tick();
+        let mut step = Vec3::zero();
+        if self.held_keys.contains(&KeyCode::W) {
+            step += self.cam.facing();
+        }
+        if self.held_keys.contains(&KeyCode::S) {
+            step -= self.cam.facing();
+        }
+        if self.held_keys.contains(&KeyCode::D) {
+            step += self.cam.right();
+        }
+        if self.held_keys.contains(&KeyCode::A) {
+            step -= self.cam.right();
+        }
+        if step != Vec3::zero() {
+            self.pos += step.normalize() * MOVE_SPEED * delta;
+        }
+
+        self.obstacles.flush(ctx);
+        self.bindings.vertex_buffers[1] = self.obstacles.buffer();
     }
 
     fn mouse_delta_event(&mut self, _ctx: &mut Context, x: f32, y: f32) {
         self.cam.turn(y * 0.1, -x * 0.1);
     }
 
+    fn mouse_motion_event(&mut self, _ctx: &mut Context, x: f32, y: f32) {
+        self.mouse_pos = (x, y);
+    }
+
+    fn key_down_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _: KeyMods, _: bool) {
+        self.held_keys.insert(keycode);
+    }
+
+    fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _: KeyMods) {
+        self.held_keys.remove(&keycode);
+    }
+
     fn draw(&mut self, ctx: &mut Context) {
         // model-view-projection matrix
         let (width, height) = ctx.screen_size();
@@ -131,14 +267,28 @@ impl EventHandler for Stage {
         );
         let view_proj = proj * view;
 
-        ctx.begin_default_pass(Default::default());
-
+        // pass 1: obstacles, rendered into the offscreen color/depth textures
+        ctx.begin_pass(self.offscreen_pass, PassAction::clear_color(0.1, 0.1, 0.1, 1.0));
         ctx.apply_pipeline(&self.pipeline);
         ctx.apply_bindings(&self.bindings);
         ctx.apply_uniforms(&shader::Uniforms { view_proj });
         ctx.draw(0, 24, self.obstacles.len() as i32);
         ctx.end_render_pass();
 
+        // pass 2: the offscreen color texture, sampled onto a fullscreen quad;
+        // `time`/`resolution`/`mouse` are refreshed every frame so this fragment shader
+        // can be swapped for a ShaderToy one with no extra glue
+        ctx.begin_default_pass(Default::default());
+        ctx.apply_pipeline(&self.quad_pipeline);
+        ctx.apply_bindings(&self.quad_bindings);
+        ctx.apply_uniforms(&quad_shader::Uniforms {
+            time: self.clock.time(),
+            resolution: (width, height),
+            mouse: self.mouse_pos,
+        });
+        ctx.draw(0, 6, 1);
+        ctx.end_render_pass();
+
         ctx.commit_frame();
     }
 }
@@ -149,6 +299,71 @@ fn main() {
     });
 }
 
+// a growable `Buffer::stream` with a CPU-side `Vec<T>` in front of it; push/extend/clear
+// like a normal `Vec`, then call `flush` to upload, growing the GPU buffer if needed
+struct BufferVec<T> {
+    cpu: Vec<T>,
+    gpu: Buffer,
+    gpu_capacity: usize,
+    buffer_type: BufferType,
+}
+
+impl<T> BufferVec<T> {
+    const INITIAL_CAPACITY: usize = 64;
+
+    fn new(ctx: &mut Context, buffer_type: BufferType) -> BufferVec<T> {
+        let gpu_capacity = Self::INITIAL_CAPACITY;
+        let gpu = Buffer::stream(ctx, buffer_type, gpu_capacity * std::mem::size_of::<T>());
+
+        BufferVec {
+            cpu: Vec::new(),
+            gpu,
+            gpu_capacity,
+            buffer_type,
+        }
+    }
+
+    fn push(&mut self, item: T) {
+        self.cpu.push(item);
+    }
+
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        self.cpu.extend(items);
+    }
+
+    fn clear(&mut self) {
+        self.cpu.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.cpu.len()
+    }
+
+    // re-read after every `flush`: a capacity grow replaces the handle
+    fn buffer(&self) -> Buffer {
+        self.gpu
+    }
+
+    fn flush(&mut self, ctx: &mut Context) {
+        if self.cpu.len() > self.gpu_capacity {
+            let mut new_capacity = self.gpu_capacity.max(1);
+            while new_capacity < self.cpu.len() {
+                new_capacity *= 2;
+            }
+
+            self.gpu.delete();
+            self.gpu = Buffer::stream(
+                ctx,
+                self.buffer_type,
+                new_capacity * std::mem::size_of::<T>(),
+            );
+            self.gpu_capacity = new_capacity;
+        }
+
+        self.gpu.update(ctx, &self.cpu[..]);
+    }
+}
+
 mod shader {
     use miniquad::*;
 
@@ -170,7 +385,7 @@ mod shader {
 
     pub const FRAGMENT: &str = r#"#version 100
     varying lowp vec4 color;
-    
+
     void main() {
         gl_FragColor = color;
     }
@@ -191,3 +406,254 @@ mod shader {
     }
 }
 
+// samples the offscreen pass's color texture onto a fullscreen quad
+mod quad_shader {
+    use miniquad::*;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec2 pos;
+    attribute vec2 uv;
+
+    varying lowp vec2 texcoord;
+
+    void main() {
+        gl_Position = vec4(pos, 0.0, 1.0);
+        texcoord = uv;
+    }
+    "#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec2 texcoord;
+
+    uniform sampler2D tex;
+    uniform float time;
+    uniform vec2 resolution;
+    uniform vec2 mouse;
+
+    void main() {
+        lowp vec4 color = texture2D(tex, texcoord);
+        // a gentle, ever-so-slight pulse
+        gl_FragColor = color * (0.95 + 0.05 * sin(time));
+    }
+    "#;
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec!["tex".to_string()],
+            uniforms: UniformBlockLayout {
+                uniforms: vec![
+                    UniformDesc::new("time", UniformType::Float1),
+                    UniformDesc::new("resolution", UniformType::Float2),
+                    UniformDesc::new("mouse", UniformType::Float2),
+                ],
+            },
+        }
+    }
+
+    #[repr(C)]
+    pub struct Uniforms {
+        pub time: f32,
+        pub resolution: (f32, f32),
+        pub mouse: (f32, f32),
+    }
+}
+
+// a minimal Wavefront OBJ/MTL loader
+mod model {
+    use miniquad::*;
+    use std::collections::HashMap;
+
+    #[derive(Clone, Copy)]
+    struct Vertex {
+        pos: [f32; 3],
+        normal: [f32; 3],
+        uv: [f32; 2],
+    }
+
+    #[derive(Clone, Default)]
+    pub struct Material {
+        pub name: String,
+        pub diffuse: [f32; 3],
+        pub specular: [f32; 3],
+        pub shininess: f32,
+        pub diffuse_texture: Option<String>,
+    }
+
+    pub struct Mesh {
+        pub bindings: Bindings,
+        pub material: Material,
+    }
+
+    // uploads one immutable vertex/index buffer pair per material; vertices are
+    // deduplicated by their (position, normal, uv) triple
+    pub fn load_obj(ctx: &mut Context, obj_bytes: &[u8], mtl_bytes: &[u8]) -> Vec<Mesh> {
+        let materials = parse_mtl(mtl_bytes);
+
+        let mut positions: Vec<[f32; 3]> = vec![];
+        let mut normals: Vec<[f32; 3]> = vec![];
+        let mut uvs: Vec<[f32; 2]> = vec![];
+
+        // one (vertices, indices) accumulator per material, keyed by material name
+        let mut groups: Vec<(String, HashMap<(u32, u32, u32), u16>, Vec<Vertex>, Vec<u16>)> =
+            vec![("default".to_string(), HashMap::new(), vec![], vec![])];
+        let mut current_group = 0;
+
+        let obj_text = std::str::from_utf8(obj_bytes).expect("OBJ file must be valid UTF-8");
+        for line in obj_text.lines() {
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => positions.push(parse_vec3(tokens)),
+                Some("vn") => normals.push(parse_vec3(tokens)),
+                Some("vt") => {
+                    let u: f32 = tokens.next().unwrap().parse().unwrap();
+                    let v: f32 = tokens.next().map(|v| v.parse().unwrap()).unwrap_or(0.0);
+                    uvs.push([u, v]);
+                }
+                Some("usemtl") => {
+                    let name = tokens.next().unwrap_or("default").to_string();
+                    current_group = match groups.iter().position(|(n, ..)| *n == name) {
+                        Some(i) => i,
+                        None => {
+                            groups.push((name, HashMap::new(), vec![], vec![]));
+                            groups.len() - 1
+                        }
+                    };
+                }
+                Some("f") => {
+                    // fan-triangulate n-gons: (0, i, i + 1) for i in 1..n-1
+                    let corners: Vec<&str> = tokens.collect();
+                    let (_, dedup, vertices, indices) = &mut groups[current_group];
+                    let resolved: Vec<(u32, u32, u32)> = corners
+                        .iter()
+                        .map(|c| resolve_face_corner(c, positions.len(), normals.len(), uvs.len()))
+                        .collect();
+
+                    for i in 1..resolved.len() - 1 {
+                        for key in [resolved[0], resolved[i], resolved[i + 1]] {
+                            let index = *dedup.entry(key).or_insert_with(|| {
+                                let (p, t, n) = key;
+                                let vertex = Vertex {
+                                    pos: positions[(p - 1) as usize],
+                                    uv: if t != 0 { uvs[(t - 1) as usize] } else { [0.0, 0.0] },
+                                    normal: if n != 0 {
+                                        normals[(n - 1) as usize]
+                                    } else {
+                                        [0.0, 1.0, 0.0]
+                                    },
+                                };
+                                vertices.push(vertex);
+                                assert!(
+                                    vertices.len() <= u16::MAX as usize + 1,
+                                    "model::load_obj: more than 65536 unique vertices in one \
+                                     material group, doesn't fit a u16 index buffer"
+                                );
+                                (vertices.len() - 1) as u16
+                            });
+                            indices.push(index);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        groups
+            .into_iter()
+            .filter(|(_, _, vertices, _)| !vertices.is_empty())
+            .map(|(name, _, vertices, indices)| {
+                let vertex_buffer = Buffer::immutable(ctx, BufferType::VertexBuffer, &vertices_as_f32(&vertices));
+                let index_buffer = Buffer::immutable(ctx, BufferType::IndexBuffer, &indices);
+                let bindings = Bindings {
+                    vertex_buffers: vec![vertex_buffer],
+                    index_buffer,
+                    images: vec![],
+                };
+                let material = materials
+                    .iter()
+                    .find(|m| m.name == name)
+                    .cloned()
+                    .unwrap_or_else(|| Material {
+                        name,
+                        ..Default::default()
+                    });
+                Mesh { bindings, material }
+            })
+            .collect()
+    }
+
+    fn vertices_as_f32(vertices: &[Vertex]) -> Vec<f32> {
+        let mut out = Vec::with_capacity(vertices.len() * 8);
+        for v in vertices {
+            out.extend_from_slice(&v.pos);
+            out.extend_from_slice(&v.normal);
+            out.extend_from_slice(&v.uv);
+        }
+        out
+    }
+
+    // a face corner looks like "12", "12/7", "12//4" or "12/7/4"; indices are
+    // 1-based, and negative indices count backwards from the end of the list seen so far
+    fn resolve_face_corner(corner: &str, pos_len: usize, normal_len: usize, uv_len: usize) -> (u32, u32, u32) {
+        let mut parts = corner.split('/');
+        let pos = resolve_index(parts.next().unwrap(), pos_len);
+        let uv = parts.next().filter(|s| !s.is_empty()).map_or(0, |s| resolve_index(s, uv_len));
+        let normal = parts.next().filter(|s| !s.is_empty()).map_or(0, |s| resolve_index(s, normal_len));
+        (pos, uv, normal)
+    }
+
+    fn resolve_index(raw: &str, len: usize) -> u32 {
+        let i: i32 = raw.parse().unwrap();
+        if i < 0 {
+            (len as i32 + i + 1) as u32
+        } else {
+            i as u32
+        }
+    }
+
+    fn parse_vec3<'a>(mut tokens: impl Iterator<Item = &'a str>) -> [f32; 3] {
+        [
+            tokens.next().unwrap().parse().unwrap(),
+            tokens.next().unwrap().parse().unwrap(),
+            tokens.next().unwrap().parse().unwrap(),
+        ]
+    }
+
+    fn parse_mtl(mtl_bytes: &[u8]) -> Vec<Material> {
+        let mut materials = vec![];
+        let mtl_text = std::str::from_utf8(mtl_bytes).unwrap_or("");
+
+        for line in mtl_text.lines() {
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("newmtl") => materials.push(Material {
+                    name: tokens.next().unwrap_or("default").to_string(),
+                    ..Default::default()
+                }),
+                Some("Kd") => {
+                    if let Some(m) = materials.last_mut() {
+                        m.diffuse = parse_vec3(tokens);
+                    }
+                }
+                Some("Ks") => {
+                    if let Some(m) = materials.last_mut() {
+                        m.specular = parse_vec3(tokens);
+                    }
+                }
+                Some("Ns") => {
+                    if let Some(m) = materials.last_mut() {
+                        m.shininess = tokens.next().unwrap().parse().unwrap();
+                    }
+                }
+                Some("map_Kd") => {
+                    if let Some(m) = materials.last_mut() {
+                        m.diffuse_texture = tokens.next().map(str::to_string);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        materials
+    }
+}
+